@@ -0,0 +1,373 @@
+use std::os::raw::{c_int, c_void};
+
+use crate::*;
+
+/// Build the `sqlite3_io_methods` table for a given `VFS` implementation, leaking it so the
+/// `'static` reference can be stashed on `VFSState` and handed to SQLite from every open file.
+///
+/// Called once per `register()`, not per open file: a function-local cache keyed only on the type
+/// parameter `V` doesn't work here, since `sqlite3_io_methods` itself never mentions `V` and rustc
+/// is free to (and does) collapse such a cache across every monomorphization, handing every VFS
+/// type in the process whichever type happened to register first.
+pub(crate) fn methods<V: VFS>() -> &'static libsqlite3_sys::sqlite3_io_methods {
+    Box::leak(Box::new(libsqlite3_sys::sqlite3_io_methods {
+        // xFetch/xUnfetch are version-3 members; SQLite's pager never even looks at them unless
+        // iVersion says so. xShm* only need version 2, so bumping this is safe either way.
+        iVersion: 3,
+        xClose: Some(x_close::<V>),
+        xRead: Some(x_read::<V>),
+        xWrite: Some(x_write::<V>),
+        xTruncate: Some(x_truncate::<V>),
+        xSync: Some(x_sync::<V>),
+        xFileSize: Some(x_file_size::<V>),
+        xLock: Some(x_lock::<V>),
+        xUnlock: Some(x_unlock::<V>),
+        xCheckReservedLock: Some(x_check_reserved_lock::<V>),
+        xFileControl: Some(x_file_control),
+        xSectorSize: Some(x_sector_size::<V>),
+        xDeviceCharacteristics: Some(x_device_characteristics::<V>),
+        #[cfg(feature = "wal")]
+        xShmMap: Some(x_shm_map::<V>),
+        #[cfg(not(feature = "wal"))]
+        xShmMap: None,
+        #[cfg(feature = "wal")]
+        xShmLock: Some(x_shm_lock::<V>),
+        #[cfg(not(feature = "wal"))]
+        xShmLock: None,
+        #[cfg(feature = "wal")]
+        xShmBarrier: Some(x_shm_barrier::<V>),
+        #[cfg(not(feature = "wal"))]
+        xShmBarrier: None,
+        #[cfg(feature = "wal")]
+        xShmUnmap: Some(x_shm_unmap::<V>),
+        #[cfg(not(feature = "wal"))]
+        xShmUnmap: None,
+        xFetch: Some(x_fetch::<V>),
+        xUnfetch: Some(x_unfetch::<V>),
+    }))
+}
+
+pub unsafe extern "C" fn x_close<V: VFS>(p_file: *mut libsqlite3_sys::sqlite3_file) -> c_int {
+    if let Some(f) = (p_file as *mut FileState<V>).as_mut() {
+        f.ext.assume_init_drop();
+    }
+    libsqlite3_sys::SQLITE_OK
+}
+
+pub unsafe extern "C" fn x_read<V: VFS>(
+    p_file: *mut libsqlite3_sys::sqlite3_file,
+    buf: *mut c_void,
+    i_amt: c_int,
+    i_ofst: i64,
+) -> c_int {
+    let file = match file_state::<V>(p_file) {
+        Ok(file) => file,
+        Err(_) => return libsqlite3_sys::SQLITE_IOERR_READ,
+    };
+
+    #[cfg(feature = "syscall")]
+    if let Some(overridden) = file.vfs.io_syscall_override("read") {
+        let result = overridden(buf, i_amt, i_ofst);
+        if result != SQLITE_SYSCALL_PASSTHROUGH {
+            return result;
+        }
+    }
+
+    let out = std::slice::from_raw_parts_mut(buf as *mut u8, i_amt as usize);
+    match file.handle.read_exact_at(out, i_ofst as u64) {
+        Ok(()) => libsqlite3_sys::SQLITE_OK,
+        // The handle already zero-filled whatever it couldn't read; just report the short read.
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => libsqlite3_sys::SQLITE_IOERR_SHORT_READ,
+        Err(err) => file.vfs.set_last_error(libsqlite3_sys::SQLITE_IOERR_READ, err),
+    }
+}
+
+pub unsafe extern "C" fn x_write<V: VFS>(
+    p_file: *mut libsqlite3_sys::sqlite3_file,
+    z: *const c_void,
+    i_amt: c_int,
+    i_ofst: i64,
+) -> c_int {
+    let file = match file_state::<V>(p_file) {
+        Ok(file) => file,
+        Err(_) => return libsqlite3_sys::SQLITE_IOERR_WRITE,
+    };
+
+    #[cfg(feature = "syscall")]
+    if let Some(overridden) = file.vfs.io_syscall_override("write") {
+        let result = overridden(z as *mut c_void, i_amt, i_ofst);
+        if result != SQLITE_SYSCALL_PASSTHROUGH {
+            return result;
+        }
+    }
+
+    let data = std::slice::from_raw_parts(z as *const u8, i_amt as usize);
+    match file.handle.write_all_at(data, i_ofst as u64) {
+        Ok(()) => libsqlite3_sys::SQLITE_OK,
+        Err(err) => file.vfs.set_last_error(libsqlite3_sys::SQLITE_IOERR_WRITE, err),
+    }
+}
+
+pub unsafe extern "C" fn x_truncate<V: VFS>(
+    p_file: *mut libsqlite3_sys::sqlite3_file,
+    size: i64,
+) -> c_int {
+    let file = match file_state::<V>(p_file) {
+        Ok(file) => file,
+        Err(_) => return libsqlite3_sys::SQLITE_IOERR_TRUNCATE,
+    };
+
+    match file.handle.truncate(size as u64) {
+        Ok(()) => libsqlite3_sys::SQLITE_OK,
+        Err(err) => file.vfs.set_last_error(libsqlite3_sys::SQLITE_IOERR_TRUNCATE, err),
+    }
+}
+
+pub unsafe extern "C" fn x_sync<V: VFS>(
+    p_file: *mut libsqlite3_sys::sqlite3_file,
+    flags: c_int,
+) -> c_int {
+    let file = match file_state::<V>(p_file) {
+        Ok(file) => file,
+        Err(_) => return libsqlite3_sys::SQLITE_IOERR_FSYNC,
+    };
+
+    let data_only = flags & libsqlite3_sys::SQLITE_SYNC_DATAONLY != 0;
+    match file.handle.sync(data_only) {
+        Ok(()) => libsqlite3_sys::SQLITE_OK,
+        Err(err) => file.vfs.set_last_error(libsqlite3_sys::SQLITE_IOERR_FSYNC, err),
+    }
+}
+
+pub unsafe extern "C" fn x_file_size<V: VFS>(
+    p_file: *mut libsqlite3_sys::sqlite3_file,
+    p_size: *mut i64,
+) -> c_int {
+    let file = match file_state::<V>(p_file) {
+        Ok(file) => file,
+        Err(_) => return libsqlite3_sys::SQLITE_IOERR_FSTAT,
+    };
+
+    match file.handle.size() {
+        Ok(size) => {
+            *p_size = size as i64;
+            libsqlite3_sys::SQLITE_OK
+        }
+        Err(err) => file.vfs.set_last_error(libsqlite3_sys::SQLITE_IOERR_FSTAT, err),
+    }
+}
+
+/// SQLite calls this unconditionally for a handful of built-in file-control opcodes (e.g. to
+/// probe the page size or journal mode), so unlike the other optional `sqlite3_io_methods` slots
+/// this one must always be present. We don't support any opcodes, so just report that.
+pub unsafe extern "C" fn x_file_control(
+    _p_file: *mut libsqlite3_sys::sqlite3_file,
+    _op: c_int,
+    _p_arg: *mut c_void,
+) -> c_int {
+    libsqlite3_sys::SQLITE_NOTFOUND
+}
+
+#[cfg(feature = "wal")]
+pub unsafe extern "C" fn x_shm_map<V: VFS>(
+    p_file: *mut libsqlite3_sys::sqlite3_file,
+    i_region: c_int,
+    sz_region: c_int,
+    b_extend: c_int,
+    pp: *mut *mut c_void,
+) -> c_int {
+    let file = match file_state::<V>(p_file) {
+        Ok(file) => file,
+        Err(_) => return libsqlite3_sys::SQLITE_IOERR_SHMMAP,
+    };
+
+    let wal = file
+        .wal_index
+        .get_or_insert_with(<_ as Default>::default);
+    match wal.map(i_region as u32, sz_region as usize, b_extend != 0) {
+        Ok(region) => {
+            *pp = region.cast::<c_void>();
+            libsqlite3_sys::SQLITE_OK
+        }
+        // SQLite probes for a not-yet-allocated region with bExtend=0 to mean "don't create it",
+        // expecting SQLITE_OK with a null pointer back, not an error.
+        Err(err) if b_extend == 0 && err.kind() == ErrorKind::NotFound => {
+            *pp = std::ptr::null_mut();
+            libsqlite3_sys::SQLITE_OK
+        }
+        Err(err) => file.vfs.set_last_error(libsqlite3_sys::SQLITE_IOERR_SHMMAP, err),
+    }
+}
+
+#[cfg(feature = "wal")]
+pub unsafe extern "C" fn x_shm_lock<V: VFS>(
+    p_file: *mut libsqlite3_sys::sqlite3_file,
+    offset: c_int,
+    n: c_int,
+    flags: c_int,
+) -> c_int {
+    let file = match file_state::<V>(p_file) {
+        Ok(file) => file,
+        Err(_) => return libsqlite3_sys::SQLITE_IOERR_SHMLOCK,
+    };
+
+    let lock_flags = if flags & libsqlite3_sys::SQLITE_SHM_UNLOCK != 0 {
+        WalLockFlags::Unlock
+    } else if flags & libsqlite3_sys::SQLITE_SHM_EXCLUSIVE != 0 {
+        WalLockFlags::Exclusive
+    } else {
+        WalLockFlags::Shared
+    };
+
+    let wal = file.wal_index.get_or_insert_with(<_ as Default>::default);
+    match wal.lock(offset as u8, n as u8, lock_flags) {
+        Ok(true) => libsqlite3_sys::SQLITE_OK,
+        Ok(false) => libsqlite3_sys::SQLITE_BUSY,
+        Err(err) => file.vfs.set_last_error(libsqlite3_sys::SQLITE_IOERR_SHMLOCK, err),
+    }
+}
+
+#[cfg(feature = "wal")]
+pub unsafe extern "C" fn x_shm_barrier<V: VFS>(p_file: *mut libsqlite3_sys::sqlite3_file) {
+    if let Ok(file) = file_state::<V>(p_file) {
+        if let Some(wal) = file.wal_index.as_mut() {
+            wal.barrier();
+        }
+    }
+}
+
+#[cfg(feature = "wal")]
+pub unsafe extern "C" fn x_shm_unmap<V: VFS>(
+    p_file: *mut libsqlite3_sys::sqlite3_file,
+    delete_flag: c_int,
+) -> c_int {
+    let file = match file_state::<V>(p_file) {
+        Ok(file) => file,
+        Err(_) => return libsqlite3_sys::SQLITE_IOERR_SHMMAP,
+    };
+
+    if let Some(mut wal) = file.wal_index.take() {
+        if let Err(err) = wal.unmap(delete_flag != 0) {
+            return file.vfs.set_last_error(libsqlite3_sys::SQLITE_IOERR_SHMMAP, err);
+        }
+    }
+
+    libsqlite3_sys::SQLITE_OK
+}
+
+pub unsafe extern "C" fn x_fetch<V: VFS>(
+    p_file: *mut libsqlite3_sys::sqlite3_file,
+    i_ofst: i64,
+    i_amt: c_int,
+    pp: *mut *mut c_void,
+) -> c_int {
+    let fetched = match file_state::<V>(p_file) {
+        Ok(file) => file.handle.fetch(i_ofst as u64, i_amt as usize),
+        Err(_) => None,
+    };
+
+    *pp = fetched.map_or(std::ptr::null_mut(), |ptr| ptr as *mut c_void);
+    libsqlite3_sys::SQLITE_OK
+}
+
+pub unsafe extern "C" fn x_unfetch<V: VFS>(
+    p_file: *mut libsqlite3_sys::sqlite3_file,
+    i_ofst: i64,
+    p: *mut c_void,
+) -> c_int {
+    if let Ok(file) = file_state::<V>(p_file) {
+        file.handle.unfetch(i_ofst as u64, p as *mut u8);
+    }
+    libsqlite3_sys::SQLITE_OK
+}
+
+pub unsafe extern "C" fn x_device_characteristics<V: VFS>(
+    p_file: *mut libsqlite3_sys::sqlite3_file,
+) -> c_int {
+    match file_state::<V>(p_file) {
+        Ok(file) => file.handle.device_characteristics(),
+        Err(_) => 0,
+    }
+}
+
+pub unsafe extern "C" fn x_sector_size<V: VFS>(p_file: *mut libsqlite3_sys::sqlite3_file) -> c_int {
+    match file_state::<V>(p_file) {
+        Ok(file) => file.handle.sector_size(),
+        Err(_) => 0,
+    }
+}
+
+fn lock_kind_from_sqlite(level: c_int) -> Option<LockKind> {
+    match level {
+        libsqlite3_sys::SQLITE_LOCK_NONE => Some(LockKind::None),
+        libsqlite3_sys::SQLITE_LOCK_SHARED => Some(LockKind::Shared),
+        libsqlite3_sys::SQLITE_LOCK_RESERVED => Some(LockKind::Reserved),
+        libsqlite3_sys::SQLITE_LOCK_PENDING => Some(LockKind::Pending),
+        libsqlite3_sys::SQLITE_LOCK_EXCLUSIVE => Some(LockKind::Exclusive),
+        _ => None,
+    }
+}
+
+pub unsafe extern "C" fn x_lock<V: VFS>(
+    p_file: *mut libsqlite3_sys::sqlite3_file,
+    e_lock: c_int,
+) -> c_int {
+    let file = match file_state::<V>(p_file) {
+        Ok(file) => file,
+        Err(_) => return libsqlite3_sys::SQLITE_IOERR_LOCK,
+    };
+
+    let to = match lock_kind_from_sqlite(e_lock) {
+        Some(kind) => kind,
+        None => return libsqlite3_sys::SQLITE_IOERR_LOCK,
+    };
+
+    if to == LockKind::Exclusive {
+        // SQLite always passes through a Pending lock on its way to Exclusive, so that no new
+        // Shared locks can be granted elsewhere while it waits for existing readers to drain.
+        // Refuse to skip straight past it.
+        if !file.handle.lock(LockKind::Pending) {
+            return libsqlite3_sys::SQLITE_BUSY;
+        }
+    }
+
+    if file.handle.lock(to) {
+        libsqlite3_sys::SQLITE_OK
+    } else {
+        libsqlite3_sys::SQLITE_BUSY
+    }
+}
+
+pub unsafe extern "C" fn x_unlock<V: VFS>(
+    p_file: *mut libsqlite3_sys::sqlite3_file,
+    e_lock: c_int,
+) -> c_int {
+    let file = match file_state::<V>(p_file) {
+        Ok(file) => file,
+        Err(_) => return libsqlite3_sys::SQLITE_IOERR_UNLOCK,
+    };
+
+    let to = match lock_kind_from_sqlite(e_lock) {
+        Some(kind) => kind,
+        None => return libsqlite3_sys::SQLITE_IOERR_UNLOCK,
+    };
+
+    if file.handle.unlock(to) {
+        libsqlite3_sys::SQLITE_OK
+    } else {
+        libsqlite3_sys::SQLITE_IOERR_UNLOCK
+    }
+}
+
+pub unsafe extern "C" fn x_check_reserved_lock<V: VFS>(
+    p_file: *mut libsqlite3_sys::sqlite3_file,
+    p_res_out: *mut c_int,
+) -> c_int {
+    let file = match file_state::<V>(p_file) {
+        Ok(file) => file,
+        Err(_) => return libsqlite3_sys::SQLITE_IOERR_CHECKRESERVEDLOCK,
+    };
+
+    *p_res_out = file.handle.reserved() as c_int;
+    libsqlite3_sys::SQLITE_OK
+}
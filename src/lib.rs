@@ -5,10 +5,14 @@ use std::{
     mem::MaybeUninit,
     ptr::null_mut,
     sync::{Arc, Mutex},
+    time::Duration,
 };
 
 use tracing::debug;
 
+#[cfg(feature = "syscall")]
+use std::collections::HashMap;
+
 // #[no_mangle]
 // pub extern "C" fn sqlite3_testvfs_init(
 //   db: *mut sqlite_ffi::db,
@@ -21,20 +25,154 @@ use tracing::debug;
 //   sqlite_ffi::SQLITE_OK
 // }
 
-pub trait VFS {
-    fn x_open(&self);
+/// A custom SQLite VFS implementation. `T` owns whatever state the VFS needs (a root directory,
+/// a network handle, an in-memory map, ...) and hands out `DatabaseHandle`s that SQLite then
+/// drives through `io_methods`.
+pub trait VFS: Sync {
+    /// The open-file handle returned by `x_open` and driven by the `io_methods` shims.
+    type DatabaseHandle: DatabaseHandle;
+
+    /// Open (and create, if requested by `flags`) the database file at `path`.
+    fn x_open(&self, path: &str, flags: i32) -> Result<Self::DatabaseHandle, std::io::Error>;
+
+    /// Delete the file at `path`. If `sync_dir` is set, the containing directory's metadata must
+    /// be synced afterwards so the deletion survives a crash.
+    fn delete(&self, path: &str, sync_dir: bool) -> Result<(), std::io::Error>;
+
+    /// Check whether `path` satisfies `flags`.
+    fn access(&self, path: &str, flags: AccessFlags) -> bool;
+
+    /// Canonicalize `path`, writing it into `out` and returning the written prefix as a `&str`.
+    fn full_pathname<'a>(&self, path: &str, out: &'a mut [u8]) -> &'a str;
+
+    /// Fill `buf` with random bytes, used by SQLite to seed rowids and the like.
+    fn randomness(&self, buf: &mut [u8]);
+
+    /// Sleep for approximately `duration`, returning how long was actually slept.
+    fn sleep(&self, duration: Duration) -> Duration;
+
+    /// The current time, expressed as a Julian day number.
+    fn current_time(&self) -> f64;
+}
+
+/// The check performed by `VFS::access`, mirroring SQLite's `SQLITE_ACCESS_*` constants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessFlags {
+    /// Does the file exist at all.
+    Exists,
+    /// Is the file readable.
+    Read,
+    /// Is the file readable and writable.
+    ReadWrite,
+}
+
+/// An open database (or journal/WAL) file, as handed back by `VFS::x_open`.
+pub trait DatabaseHandle {
+    /// Current size of the file in bytes.
+    fn size(&self) -> Result<u64, std::io::Error>;
+
+    /// Read exactly `buf.len()` bytes starting at `offset`.
+    ///
+    /// If the file is shorter than `offset + buf.len()`, the implementation must still fill in
+    /// zeros for whatever portion of `buf` could not be read and return an error with
+    /// [`ErrorKind::UnexpectedEof`] so the caller can report `SQLITE_IOERR_SHORT_READ`.
+    fn read_exact_at(&mut self, buf: &mut [u8], offset: u64) -> Result<(), std::io::Error>;
+
+    /// Write `buf` at `offset`, extending the file if necessary.
+    fn write_all_at(&self, buf: &[u8], offset: u64) -> Result<(), std::io::Error>;
+
+    /// Resize the file to exactly `size` bytes.
+    fn truncate(&self, size: u64) -> Result<(), std::io::Error>;
+
+    /// Flush any buffered writes to durable storage. When `data_only` is set the file's metadata
+    /// does not need to be synced, only its contents.
+    fn sync(&self, data_only: bool) -> Result<(), std::io::Error>;
+
+    /// The shared-memory backing used for WAL mode's `-shm` index. Only required when the `wal`
+    /// feature is enabled, so VFS implementations that never open WAL databases pay nothing for
+    /// it. [`HeapWalIndex`] is a ready-made, in-process implementation.
+    #[cfg(feature = "wal")]
+    type WalIndex: WalIndex + Default;
+
+    /// Request a direct pointer to a memory-mapped view of `amount` bytes at `offset`, letting
+    /// SQLite read through it instead of copying via `read_exact_at`. The default returns `None`,
+    /// which makes SQLite fall back to `xRead`; a handle backed by an mmap'd file or a memory
+    /// buffer can return a pointer that stays valid until the matching `unfetch`.
+    fn fetch(&self, offset: u64, amount: usize) -> Option<*mut u8> {
+        let _ = (offset, amount);
+        None
+    }
+
+    /// Release a pointer previously returned by `fetch`.
+    fn unfetch(&self, offset: u64, ptr: *mut u8) {
+        let _ = (offset, ptr);
+    }
+
+    /// Bitmask of `SQLITE_IOCAP_*` flags describing this file's write semantics. Defaults to 0
+    /// (no special guarantees advertised).
+    fn device_characteristics(&self) -> i32 {
+        0
+    }
+
+    /// The device's sector size in bytes, used by SQLite to decide atomic-write boundaries.
+    /// Defaults to 0, letting SQLite fall back to its own default.
+    fn sector_size(&self) -> i32 {
+        0
+    }
+
+    /// Attempt to raise the file's lock to `to`. Returns `false`, rather than an error, when a
+    /// conflicting lock held by another connection prevents it.
+    fn lock(&self, to: LockKind) -> bool;
+
+    /// Attempt to lower the file's lock to `to`. Returns `false` if it could not be lowered.
+    fn unlock(&self, to: LockKind) -> bool;
+
+    /// Whether any connection, this one included, currently holds at least a `Reserved` lock on
+    /// this file.
+    fn reserved(&self) -> bool;
+}
+
+/// SQLite's file-locking ladder, from weakest to strongest: `None` < `Shared` < `Reserved` <
+/// `Pending` < `Exclusive`. `x_lock` takes `Pending` on the way to `Exclusive`; an unlock may drop
+/// to any lower rung in a single call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum LockKind {
+    #[default]
+    None,
+    Shared,
+    Reserved,
+    Pending,
+    Exclusive,
 }
 
 struct VFSState<T: VFS + Sized> {
     vfs: Arc<T>,
     last_error: Arc<Mutex<Option<(i32, std::io::Error)>>>, // sqlite error, rust error
+    /// The `sqlite3_io_methods` table every file opened through this VFS shares, built once at
+    /// `register()` time (see `io_methods::methods`).
+    methods: &'static libsqlite3_sys::sqlite3_io_methods,
+    /// Overrides installed via `xSetSystemCall`, keyed by syscall name (e.g. `"open"`, `"write"`).
+    /// A name absent from the map means "use the default implementation".
+    #[cfg(feature = "syscall")]
+    syscalls: Mutex<HashMap<CString, libsqlite3_sys::sqlite3_syscall_ptr>>,
 }
 
 /// FileState is a wrapper around the sqlite3_file struct that contains the VFS state.
 /// Because SQLite allocates this initially, the ext might not exist, so we use a MaybeUninit.
+#[repr(C)]
 struct FileState<T: VFS + Sized> {
     base: libsqlite3_sys::sqlite3_file,
-    ext: MaybeUninit<Arc<VFSState<T>>>, // TODO: I think this needs to be a "file-specific" pointer, even if just a thin proxy for referencing the VFS again through an Arc
+    ext: MaybeUninit<FileExt<T>>,
+}
+
+/// The part of `FileState` that we populate once `x_open` succeeds: a handle back to the shared
+/// VFS state (for error reporting) and the per-file handle the VFS implementation returned.
+struct FileExt<T: VFS + Sized> {
+    vfs: Arc<VFSState<T>>,
+    handle: T::DatabaseHandle,
+    /// Lazily created the first time SQLite calls `xShmMap` on this file.
+    #[cfg(feature = "wal")]
+    wal_index: Option<<T::DatabaseHandle as DatabaseHandle>::WalIndex>,
 }
 
 impl<T: VFS + Sized> VFSState<T> {
@@ -44,12 +182,46 @@ impl<T: VFS + Sized> VFSState<T> {
         self.last_error.lock().unwrap().insert((code, error));
         return code;
     }
+
+    /// Look up the override installed for `name` via `xSetSystemCall`, if any, transmuted to the
+    /// signature `x_read`/`x_write` actually call it with.
+    #[cfg(feature = "syscall")]
+    unsafe fn io_syscall_override(&self, name: &str) -> Option<IoSyscallOverride> {
+        let key = CString::new(name).ok()?;
+        let ptr = self.syscalls.lock().unwrap().get(&key).copied().flatten()?;
+        Some(std::mem::transmute::<
+            unsafe extern "C" fn(),
+            IoSyscallOverride,
+        >(ptr))
+    }
 }
 
+/// Signature for the `read`/`write` syscall overrides test code installs via `xSetSystemCall`,
+/// mirroring the buffer/amount/offset shape of the I/O method being intercepted. Return an
+/// `SQLITE_*` result code to report it to SQLite immediately, or [`SQLITE_SYSCALL_PASSTHROUGH`] to
+/// fall through to the VFS's real `DatabaseHandle` implementation.
+#[cfg(feature = "syscall")]
+pub type IoSyscallOverride =
+    unsafe extern "C" fn(*mut std::os::raw::c_void, std::os::raw::c_int, i64) -> std::os::raw::c_int;
+
+/// Sentinel an [`IoSyscallOverride`] returns to mean "I declined to intercept this call".
+#[cfg(feature = "syscall")]
+pub const SQLITE_SYSCALL_PASSTHROUGH: std::os::raw::c_int = -1;
+
 fn null_ptr_error() -> std::io::Error {
     std::io::Error::new(ErrorKind::Other, "received null pointer")
 }
 
+/// Borrow a C string handed to us by SQLite as a `&str`, treating a null pointer as empty.
+unsafe fn cstr_to_str<'a>(ptr: *const c_char) -> Result<&'a str, std::io::Error> {
+    if ptr.is_null() {
+        return Ok("");
+    }
+    std::ffi::CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|err| std::io::Error::new(ErrorKind::InvalidInput, err))
+}
+
 unsafe fn vfs_state<'a, V: VFS + Sync + Sized>(
     ptr: *mut libsqlite3_sys::sqlite3_vfs,
 ) -> Result<&'a mut Arc<VFSState<V>>, std::io::Error> {
@@ -62,7 +234,7 @@ unsafe fn vfs_state<'a, V: VFS + Sync + Sized>(
 
 unsafe fn file_state<'a, V: VFS + Sync + Sized>(
     ptr: *mut libsqlite3_sys::sqlite3_file,
-) -> Result<&'a mut Arc<VFSState<V>>, std::io::Error> {
+) -> Result<&'a mut FileExt<V>, std::io::Error> {
     let f = (ptr as *mut FileState<V>)
         .as_mut()
         .ok_or_else(null_ptr_error)?;
@@ -103,35 +275,18 @@ impl From<std::ffi::NulError> for RegisterError {
 }
 
 mod io_methods;
+#[cfg(feature = "wal")]
+mod wal;
 mod vfs;
 
+#[cfg(feature = "wal")]
+pub use wal::{HeapWalIndex, WalIndex, WalLockFlags};
+
 pub fn register<T: VFS + Sync + Sized>(
     name: &str,
     as_default: bool,
     vfs: T,
 ) -> Result<(), RegisterError> {
-    let io_methods = libsqlite3_sys::sqlite3_io_methods {
-        iVersion: 2,
-        xClose: Some(io_methods::x_close),
-        xRead: None,
-        xWrite: None,
-        xTruncate: None,
-        xSync: None,
-        xFileSize: None,
-        xLock: None,
-        xUnlock: None,
-        xCheckReservedLock: None,
-        xFileControl: None,
-        xSectorSize: None,
-        xDeviceCharacteristics: None,
-        xShmMap: None,
-        xShmLock: None,
-        xShmBarrier: None,
-        xShmUnmap: None,
-        xFetch: None,
-        xUnfetch: None,
-    };
-
     // Leak the VFS name so its memory remains valid.
     let name_ptr = CString::new(name)?.into_raw();
 
@@ -139,6 +294,9 @@ pub fn register<T: VFS + Sync + Sized>(
     let state = Arc::new(VFSState {
         vfs: Arc::new(vfs),
         last_error: Arc::new(Mutex::new(None)),
+        methods: io_methods::methods::<T>(),
+        #[cfg(feature = "syscall")]
+        syscalls: Mutex::new(HashMap::new()),
     });
     let ptr = Box::into_raw(Box::new(state));
 
@@ -153,17 +311,17 @@ pub fn register<T: VFS + Sync + Sized>(
         zName: name_ptr,
         pAppData: ptr as _,
         xOpen: Some(vfs::x_open::<T>),
-        xDelete: None,
-        xAccess: None,
-        xFullPathname: None,
+        xDelete: Some(vfs::x_delete::<T>),
+        xAccess: Some(vfs::x_access::<T>),
+        xFullPathname: Some(vfs::x_full_pathname::<T>),
         xDlOpen: None,
         xDlError: None,
         xDlSym: None,
         xDlClose: None,
-        xRandomness: None,
-        xSleep: None,
-        xCurrentTime: None,
-        xGetLastError: None,
+        xRandomness: Some(vfs::x_randomness::<T>),
+        xSleep: Some(vfs::x_sleep::<T>),
+        xCurrentTime: Some(vfs::x_current_time::<T>),
+        xGetLastError: Some(vfs::x_get_last_error::<T>),
         xCurrentTimeInt64: None,
         #[cfg(not(feature = "syscall"))]
         xSetSystemCall: None,
@@ -172,11 +330,11 @@ pub fn register<T: VFS + Sync + Sized>(
         #[cfg(not(feature = "syscall"))]
         xNextSystemCall: None,
         #[cfg(feature = "syscall")]
-        xSetSystemCall: Some(vfs::set_system_call::<V>),
+        xSetSystemCall: Some(vfs::x_set_system_call::<T>),
         #[cfg(feature = "syscall")]
-        xGetSystemCall: Some(vfs::get_system_call::<V>),
+        xGetSystemCall: Some(vfs::x_get_system_call::<T>),
         #[cfg(feature = "syscall")]
-        xNextSystemCall: Some(vfs::next_system_call::<V>),
+        xNextSystemCall: Some(vfs::x_next_system_call::<T>),
     }));
 
     let result = unsafe { libsqlite3_sys::sqlite3_vfs_register(vfs, as_default as i32) };
@@ -189,17 +347,479 @@ pub fn register<T: VFS + Sync + Sized>(
 
 #[cfg(test)]
 mod tests {
-    use crate::{register, VFS};
+    use crate::{register, DatabaseHandle, VFS};
     use rusqlite::{Connection, OpenFlags};
-    use std::{ffi::CString, fs};
+    use std::{
+        ffi::CString,
+        fs,
+        io::ErrorKind,
+        os::raw::{c_char, c_int, c_void},
+        os::unix::{fs::FileExt as _, io::AsRawFd as _},
+    };
+
+    // The tests below occasionally drive a registered VFS directly through the raw
+    // `sqlite3_vfs`/`sqlite3_file` function pointers, the same way SQLite itself does, instead of
+    // through a `Connection`. That lets them force exact sequences of calls (a short read, lock
+    // contention between two "connections") that would be awkward or impossible to coax out of
+    // SQLite's own transaction machinery.
+
+    /// Open `path` through the already-registered VFS `vfs_name`, returning a zeroed buffer sized
+    /// the way SQLite itself would allocate a `sqlite3_file` for it.
+    unsafe fn raw_open(vfs_name: &str, path: &str) -> Box<[u8]> {
+        let c_name = CString::new(vfs_name).unwrap();
+        let vfs_ptr = libsqlite3_sys::sqlite3_vfs_find(c_name.as_ptr());
+        assert!(!vfs_ptr.is_null(), "vfs {vfs_name} not registered");
 
-    // A simple dummy VFS implementation just for testing.
+        let mut buf = vec![0u8; (*vfs_ptr).szOsFile as usize].into_boxed_slice();
+        let c_path = CString::new(path).unwrap();
+        let mut out_flags = 0;
+        let rc = ((*vfs_ptr).xOpen.unwrap())(
+            vfs_ptr,
+            c_path.as_ptr(),
+            buf.as_mut_ptr() as *mut libsqlite3_sys::sqlite3_file,
+            libsqlite3_sys::SQLITE_OPEN_READWRITE | libsqlite3_sys::SQLITE_OPEN_CREATE,
+            &mut out_flags,
+        );
+        assert_eq!(rc, libsqlite3_sys::SQLITE_OK, "xOpen failed");
+        buf
+    }
+
+    unsafe fn as_sqlite3_file(buf: &mut [u8]) -> *mut libsqlite3_sys::sqlite3_file {
+        buf.as_mut_ptr() as *mut libsqlite3_sys::sqlite3_file
+    }
+
+    unsafe fn raw_read(buf: &mut [u8], out: &mut [u8], offset: i64) -> c_int {
+        let f = as_sqlite3_file(buf);
+        ((*(*f).pMethods).xRead.unwrap())(f, out.as_mut_ptr() as *mut c_void, out.len() as c_int, offset)
+    }
+
+    unsafe fn raw_write(buf: &mut [u8], data: &[u8], offset: i64) -> c_int {
+        let f = as_sqlite3_file(buf);
+        ((*(*f).pMethods).xWrite.unwrap())(f, data.as_ptr() as *const c_void, data.len() as c_int, offset)
+    }
+
+    unsafe fn raw_lock(buf: &mut [u8], level: c_int) -> c_int {
+        let f = as_sqlite3_file(buf);
+        ((*(*f).pMethods).xLock.unwrap())(f, level)
+    }
+
+    unsafe fn raw_unlock(buf: &mut [u8], level: c_int) -> c_int {
+        let f = as_sqlite3_file(buf);
+        ((*(*f).pMethods).xUnlock.unwrap())(f, level)
+    }
+
+    unsafe fn raw_close(buf: &mut [u8]) {
+        let f = as_sqlite3_file(buf);
+        ((*(*f).pMethods).xClose.unwrap())(f);
+    }
+
+    unsafe fn raw_fetch(buf: &mut [u8], offset: i64, amount: usize) -> (c_int, *mut c_void) {
+        let f = as_sqlite3_file(buf);
+        let mut pp: *mut c_void = std::ptr::null_mut();
+        let rc = ((*(*f).pMethods).xFetch.unwrap())(f, offset, amount as c_int, &mut pp);
+        (rc, pp)
+    }
+
+    unsafe fn raw_unfetch(buf: &mut [u8], offset: i64, p: *mut c_void) -> c_int {
+        let f = as_sqlite3_file(buf);
+        ((*(*f).pMethods).xUnfetch.unwrap())(f, offset, p)
+    }
+
+    unsafe fn raw_device_characteristics(buf: &mut [u8]) -> c_int {
+        let f = as_sqlite3_file(buf);
+        ((*(*f).pMethods).xDeviceCharacteristics.unwrap())(f)
+    }
+
+    unsafe fn raw_sector_size(buf: &mut [u8]) -> c_int {
+        let f = as_sqlite3_file(buf);
+        ((*(*f).pMethods).xSectorSize.unwrap())(f)
+    }
+
+    unsafe fn raw_get_last_error(vfs_name: &str, out: &mut [u8]) -> c_int {
+        let c_name = CString::new(vfs_name).unwrap();
+        let vfs_ptr = libsqlite3_sys::sqlite3_vfs_find(c_name.as_ptr());
+        assert!(!vfs_ptr.is_null(), "vfs {vfs_name} not registered");
+        ((*vfs_ptr).xGetLastError.unwrap())(vfs_ptr, out.len() as c_int, out.as_mut_ptr() as *mut c_char)
+    }
+
+    #[cfg(feature = "syscall")]
+    unsafe fn raw_set_system_call(
+        vfs_name: &str,
+        name: &str,
+        func: libsqlite3_sys::sqlite3_syscall_ptr,
+    ) -> c_int {
+        let c_vfs_name = CString::new(vfs_name).unwrap();
+        let vfs_ptr = libsqlite3_sys::sqlite3_vfs_find(c_vfs_name.as_ptr());
+        assert!(!vfs_ptr.is_null(), "vfs {vfs_name} not registered");
+        let c_name = CString::new(name).unwrap();
+        ((*vfs_ptr).xSetSystemCall.unwrap())(vfs_ptr, c_name.as_ptr(), func)
+    }
+
+    unsafe fn raw_delete(vfs_name: &str, path: &str, sync_dir: bool) -> c_int {
+        let c_vfs_name = CString::new(vfs_name).unwrap();
+        let vfs_ptr = libsqlite3_sys::sqlite3_vfs_find(c_vfs_name.as_ptr());
+        assert!(!vfs_ptr.is_null(), "vfs {vfs_name} not registered");
+        let c_path = CString::new(path).unwrap();
+        ((*vfs_ptr).xDelete.unwrap())(vfs_ptr, c_path.as_ptr(), sync_dir as c_int)
+    }
+
+    unsafe fn raw_access(vfs_name: &str, path: &str, flags: c_int) -> c_int {
+        let c_vfs_name = CString::new(vfs_name).unwrap();
+        let vfs_ptr = libsqlite3_sys::sqlite3_vfs_find(c_vfs_name.as_ptr());
+        assert!(!vfs_ptr.is_null(), "vfs {vfs_name} not registered");
+        let c_path = CString::new(path).unwrap();
+        let mut res_out: c_int = 0;
+        ((*vfs_ptr).xAccess.unwrap())(vfs_ptr, c_path.as_ptr(), flags, &mut res_out);
+        res_out
+    }
+
+    #[cfg(feature = "syscall")]
+    unsafe fn raw_get_system_call(vfs_name: &str, name: &str) -> libsqlite3_sys::sqlite3_syscall_ptr {
+        let c_vfs_name = CString::new(vfs_name).unwrap();
+        let vfs_ptr = libsqlite3_sys::sqlite3_vfs_find(c_vfs_name.as_ptr());
+        assert!(!vfs_ptr.is_null(), "vfs {vfs_name} not registered");
+        let c_name = CString::new(name).unwrap();
+        ((*vfs_ptr).xGetSystemCall.unwrap())(vfs_ptr, c_name.as_ptr())
+    }
+
+    // `DummyVFS` and `LockTestVFS` below both sit directly on the real filesystem for
+    // `delete`/`access`/`full_pathname`; share that plumbing here instead of duplicating it.
+    fn passthrough_delete(path: &str) -> Result<(), std::io::Error> {
+        fs::remove_file(path)
+    }
+
+    fn passthrough_access(path: &str, flags: crate::AccessFlags) -> bool {
+        match flags {
+            crate::AccessFlags::Exists => fs::metadata(path).is_ok(),
+            crate::AccessFlags::Read => fs::File::open(path).is_ok(),
+            crate::AccessFlags::ReadWrite => fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(path)
+                .is_ok(),
+        }
+    }
+
+    fn passthrough_full_pathname<'a>(path: &str, out: &'a mut [u8]) -> &'a str {
+        let bytes = path.as_bytes();
+        let len = bytes.len().min(out.len());
+        out[..len].copy_from_slice(&bytes[..len]);
+        std::str::from_utf8(&out[..len]).unwrap_or("")
+    }
+
+    // A simple dummy VFS implementation, backed by plain OS files, just for demonstration.
     struct DummyVFS;
 
+    struct DummyFile(
+        fs::File,
+        std::cell::Cell<crate::LockKind>,
+        /// Buffers handed out by `fetch` and not yet released by a matching `unfetch`, keyed by
+        /// the pointer returned to the caller.
+        std::cell::RefCell<std::collections::HashMap<usize, Box<[u8]>>>,
+    );
+
     impl VFS for DummyVFS {
-        fn x_open(&self) {
-            // This is just for demonstration.
-            println!("DummyVFS::x_open was called");
+        type DatabaseHandle = DummyFile;
+
+        fn x_open(&self, path: &str, _flags: i32) -> Result<Self::DatabaseHandle, std::io::Error> {
+            println!("DummyVFS::x_open was called with path {:?}", path);
+            let file = fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)?;
+            Ok(DummyFile(
+                file,
+                std::cell::Cell::new(crate::LockKind::None),
+                std::cell::RefCell::new(std::collections::HashMap::new()),
+            ))
+        }
+
+        fn delete(&self, path: &str, _sync_dir: bool) -> Result<(), std::io::Error> {
+            passthrough_delete(path)
+        }
+
+        fn access(&self, path: &str, flags: crate::AccessFlags) -> bool {
+            passthrough_access(path, flags)
+        }
+
+        fn full_pathname<'a>(&self, path: &str, out: &'a mut [u8]) -> &'a str {
+            passthrough_full_pathname(path, out)
+        }
+
+        fn randomness(&self, buf: &mut [u8]) {
+            use std::time::{SystemTime, UNIX_EPOCH};
+
+            let mut seed = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .subsec_nanos() as u64;
+            for byte in buf.iter_mut() {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                *byte = (seed >> 32) as u8;
+            }
+        }
+
+        fn sleep(&self, duration: std::time::Duration) -> std::time::Duration {
+            std::thread::sleep(duration);
+            duration
+        }
+
+        fn current_time(&self) -> f64 {
+            use std::time::{SystemTime, UNIX_EPOCH};
+
+            let unix_days = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs_f64()
+                / 86400.0;
+            // Julian day number of the Unix epoch (1970-01-01T00:00:00Z) plus elapsed days.
+            2440587.5 + unix_days
+        }
+    }
+
+    impl DatabaseHandle for DummyFile {
+        #[cfg(feature = "wal")]
+        type WalIndex = crate::HeapWalIndex;
+
+        fn size(&self) -> Result<u64, std::io::Error> {
+            Ok(self.0.metadata()?.len())
+        }
+
+        fn read_exact_at(&mut self, buf: &mut [u8], offset: u64) -> Result<(), std::io::Error> {
+            let file_len = self.0.metadata()?.len();
+            if offset >= file_len {
+                buf.fill(0);
+                return Err(std::io::Error::new(ErrorKind::UnexpectedEof, "read past eof"));
+            }
+
+            let available = ((file_len - offset) as usize).min(buf.len());
+            self.0.read_exact_at(&mut buf[..available], offset)?;
+            if available < buf.len() {
+                buf[available..].fill(0);
+                return Err(std::io::Error::new(ErrorKind::UnexpectedEof, "short read"));
+            }
+
+            Ok(())
+        }
+
+        fn write_all_at(&self, buf: &[u8], offset: u64) -> Result<(), std::io::Error> {
+            self.0.write_all_at(buf, offset)
+        }
+
+        fn truncate(&self, size: u64) -> Result<(), std::io::Error> {
+            self.0.set_len(size)
+        }
+
+        fn sync(&self, _data_only: bool) -> Result<(), std::io::Error> {
+            self.0.sync_all()
+        }
+
+        fn fetch(&self, offset: u64, amount: usize) -> Option<*mut u8> {
+            let mut buf = vec![0u8; amount].into_boxed_slice();
+            self.0.read_exact_at(&mut buf, offset).ok()?;
+            let ptr = buf.as_mut_ptr();
+            self.2.borrow_mut().insert(ptr as usize, buf);
+            Some(ptr)
+        }
+
+        fn unfetch(&self, _offset: u64, ptr: *mut u8) {
+            self.2.borrow_mut().remove(&(ptr as usize));
+        }
+
+        fn device_characteristics(&self) -> i32 {
+            libsqlite3_sys::SQLITE_IOCAP_ATOMIC
+        }
+
+        fn sector_size(&self) -> i32 {
+            4096
+        }
+
+        fn lock(&self, to: crate::LockKind) -> bool {
+            // This demo VFS only ever serves one connection at a time, so there is nothing to
+            // contend with; just record the new lock level.
+            self.1.set(to);
+            true
+        }
+
+        fn unlock(&self, to: crate::LockKind) -> bool {
+            self.1.set(to);
+            true
+        }
+
+        fn reserved(&self) -> bool {
+            self.1.get() >= crate::LockKind::Reserved
+        }
+    }
+
+    /// Cross-connection lock state for [`LockTestFile`], modeling just enough of SQLite's real
+    /// locking ladder (shared readers are counted; `Reserved`/`Pending`/`Exclusive` are each held
+    /// by at most one connection at a time) to exercise contention between two "connections"
+    /// sharing one file, unlike [`DummyFile`]'s per-instance `Cell`.
+    #[derive(Default)]
+    struct SharedLockTable {
+        shared_holders: u32,
+        reserved: bool,
+        pending: bool,
+        exclusive: bool,
+    }
+
+    struct LockTestVFS {
+        tables: std::sync::Mutex<std::collections::HashMap<String, std::sync::Arc<std::sync::Mutex<SharedLockTable>>>>,
+    }
+
+    impl LockTestVFS {
+        fn new() -> Self {
+            Self {
+                tables: std::sync::Mutex::new(std::collections::HashMap::new()),
+            }
+        }
+    }
+
+    struct LockTestFile {
+        file: fs::File,
+        table: std::sync::Arc<std::sync::Mutex<SharedLockTable>>,
+        level: std::cell::Cell<crate::LockKind>,
+    }
+
+    impl VFS for LockTestVFS {
+        type DatabaseHandle = LockTestFile;
+
+        fn x_open(&self, path: &str, _flags: i32) -> Result<Self::DatabaseHandle, std::io::Error> {
+            let file = fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)?;
+            let table = self
+                .tables
+                .lock()
+                .unwrap()
+                .entry(path.to_string())
+                .or_insert_with(|| std::sync::Arc::new(std::sync::Mutex::new(SharedLockTable::default())))
+                .clone();
+            Ok(LockTestFile {
+                file,
+                table,
+                level: std::cell::Cell::new(crate::LockKind::None),
+            })
+        }
+
+        fn delete(&self, path: &str, _sync_dir: bool) -> Result<(), std::io::Error> {
+            passthrough_delete(path)
+        }
+
+        fn access(&self, path: &str, flags: crate::AccessFlags) -> bool {
+            passthrough_access(path, flags)
+        }
+
+        fn full_pathname<'a>(&self, path: &str, out: &'a mut [u8]) -> &'a str {
+            passthrough_full_pathname(path, out)
+        }
+
+        fn randomness(&self, buf: &mut [u8]) {
+            buf.fill(0);
+        }
+
+        fn sleep(&self, duration: std::time::Duration) -> std::time::Duration {
+            duration
+        }
+
+        fn current_time(&self) -> f64 {
+            0.0
+        }
+    }
+
+    impl DatabaseHandle for LockTestFile {
+        #[cfg(feature = "wal")]
+        type WalIndex = crate::HeapWalIndex;
+
+        fn size(&self) -> Result<u64, std::io::Error> {
+            Ok(self.file.metadata()?.len())
+        }
+
+        fn read_exact_at(&mut self, buf: &mut [u8], offset: u64) -> Result<(), std::io::Error> {
+            let file_len = self.file.metadata()?.len();
+            let available = (file_len.saturating_sub(offset) as usize).min(buf.len());
+            self.file.read_exact_at(&mut buf[..available], offset)?;
+            if available < buf.len() {
+                buf[available..].fill(0);
+                return Err(std::io::Error::new(ErrorKind::UnexpectedEof, "short read"));
+            }
+            Ok(())
+        }
+
+        fn write_all_at(&self, buf: &[u8], offset: u64) -> Result<(), std::io::Error> {
+            self.file.write_all_at(buf, offset)
+        }
+
+        fn truncate(&self, size: u64) -> Result<(), std::io::Error> {
+            self.file.set_len(size)
+        }
+
+        fn sync(&self, _data_only: bool) -> Result<(), std::io::Error> {
+            self.file.sync_all()
+        }
+
+        fn lock(&self, to: crate::LockKind) -> bool {
+            let mut table = self.table.lock().unwrap();
+            let from = self.level.get();
+            if to <= from {
+                self.level.set(to);
+                return true;
+            }
+
+            let already_shared = from >= crate::LockKind::Shared;
+            let granted = match to {
+                crate::LockKind::None => true,
+                crate::LockKind::Shared => !table.exclusive && (already_shared || !table.pending),
+                crate::LockKind::Reserved => !table.reserved,
+                crate::LockKind::Pending => !table.pending,
+                crate::LockKind::Exclusive => {
+                    let other_shared_holders = table.shared_holders - u32::from(already_shared);
+                    other_shared_holders == 0
+                }
+            };
+            if !granted {
+                return false;
+            }
+
+            if !already_shared && to >= crate::LockKind::Shared {
+                table.shared_holders += 1;
+            }
+            match to {
+                crate::LockKind::Reserved => table.reserved = true,
+                crate::LockKind::Pending => table.pending = true,
+                crate::LockKind::Exclusive => table.exclusive = true,
+                _ => {}
+            }
+            self.level.set(to);
+            true
+        }
+
+        fn unlock(&self, to: crate::LockKind) -> bool {
+            let mut table = self.table.lock().unwrap();
+            let from = self.level.get();
+            if from >= crate::LockKind::Exclusive && to < crate::LockKind::Exclusive {
+                table.exclusive = false;
+            }
+            if from >= crate::LockKind::Pending && to < crate::LockKind::Pending {
+                table.pending = false;
+            }
+            if from >= crate::LockKind::Reserved && to < crate::LockKind::Reserved {
+                table.reserved = false;
+            }
+            if from >= crate::LockKind::Shared && to < crate::LockKind::Shared {
+                table.shared_holders -= 1;
+            }
+            self.level.set(to);
+            true
+        }
+
+        fn reserved(&self) -> bool {
+            self.table.lock().unwrap().reserved
         }
     }
 
@@ -244,4 +864,419 @@ mod tests {
         drop(conn);
         let _ = fs::remove_file(db_path);
     }
+
+    #[cfg(feature = "wal")]
+    #[test]
+    fn test_wal_open_write_checkpoint() {
+        let vfs_name = "dummyvfs_wal";
+        register(vfs_name, false, DummyVFS).expect("failed to register dummy VFS");
+
+        let db_path = "dummy_wal.db";
+        let wal_path = format!("{db_path}-wal");
+        let shm_path = format!("{db_path}-shm");
+        let _ = fs::remove_file(db_path);
+        let _ = fs::remove_file(&wal_path);
+        let _ = fs::remove_file(&shm_path);
+
+        let conn = Connection::open_with_flags_and_vfs(
+            db_path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
+            vfs_name,
+        )
+        .expect("failed to open connection with dummy VFS");
+
+        let mode: String = conn
+            .query_row("PRAGMA journal_mode=WAL", [], |row| row.get(0))
+            .expect("failed to switch to WAL mode");
+        assert_eq!(mode, "wal", "xShmMap/xShmLock must work for WAL mode to engage");
+
+        conn.execute("CREATE TABLE test (id INTEGER)", [])
+            .expect("failed to create table");
+        conn.execute("INSERT INTO test (id) VALUES (1)", [])
+            .expect("failed to insert row");
+
+        let (busy, ..): (i32, i32, i32) = conn
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| {
+                Ok((row.get(0)?, row.get::<_, i32>(1)?, row.get::<_, i32>(2)?))
+            })
+            .expect("failed to checkpoint WAL");
+        assert_eq!(busy, 0, "checkpoint should not be blocked");
+
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM test", [], |row| row.get(0))
+            .expect("failed to query table");
+        assert_eq!(count, 1);
+
+        drop(conn);
+        let _ = fs::remove_file(db_path);
+        let _ = fs::remove_file(&wal_path);
+        let _ = fs::remove_file(&shm_path);
+    }
+
+    #[test]
+    fn test_short_read_reports_ioerr_and_last_error() {
+        let vfs_name = "dummyvfs_shortread";
+        register(vfs_name, false, DummyVFS).expect("failed to register dummy VFS");
+
+        let db_path = "dummy_shortread.db";
+        let _ = fs::remove_file(db_path);
+
+        unsafe {
+            let mut file = raw_open(vfs_name, db_path);
+            assert_eq!(raw_write(&mut file, b"abcd", 0), libsqlite3_sys::SQLITE_OK);
+
+            // Ask for more bytes than the file holds: DummyFile::read_exact_at zero-fills the
+            // rest and reports UnexpectedEof, which x_read must translate to
+            // SQLITE_IOERR_SHORT_READ.
+            let mut buf = [0xffu8; 16];
+            let rc = raw_read(&mut file, &mut buf, 0);
+            assert_eq!(rc, libsqlite3_sys::SQLITE_IOERR_SHORT_READ);
+            assert_eq!(&buf[..4], b"abcd");
+            assert_eq!(&buf[4..], &[0u8; 12][..], "unread tail must be zero-filled");
+
+            // Now force a genuine OS-level read error (as opposed to a short read) by closing the
+            // handle's underlying fd out from under it, and check that, unlike the short read
+            // above, xGetLastError surfaces it afterwards.
+            let fd = crate::file_state::<DummyVFS>(as_sqlite3_file(&mut file))
+                .unwrap()
+                .handle
+                .0
+                .as_raw_fd();
+            libc::close(fd);
+
+            let rc = raw_read(&mut file, &mut buf, 0);
+            assert_eq!(rc, libsqlite3_sys::SQLITE_IOERR_READ);
+
+            let mut err_buf = [0u8; 256];
+            let code = raw_get_last_error(vfs_name, &mut err_buf);
+            assert_eq!(code, libsqlite3_sys::SQLITE_IOERR_READ);
+            let message = std::ffi::CStr::from_ptr(err_buf.as_ptr() as *const c_char)
+                .to_str()
+                .unwrap();
+            assert!(
+                !message.is_empty(),
+                "xGetLastError should report the underlying OS error"
+            );
+        }
+
+        let _ = fs::remove_file(db_path);
+    }
+
+    #[test]
+    fn test_lock_kind_ladder_across_two_connections() {
+        let vfs_name = "locktestvfs";
+        register(vfs_name, false, LockTestVFS::new()).expect("failed to register lock test VFS");
+
+        let db_path = "locktest.db";
+        let _ = fs::remove_file(db_path);
+
+        unsafe {
+            let mut conn_a = raw_open(vfs_name, db_path);
+            let mut conn_b = raw_open(vfs_name, db_path);
+
+            // Both connections can hold Shared at once.
+            assert_eq!(
+                raw_lock(&mut conn_a, libsqlite3_sys::SQLITE_LOCK_SHARED),
+                libsqlite3_sys::SQLITE_OK
+            );
+            assert_eq!(
+                raw_lock(&mut conn_b, libsqlite3_sys::SQLITE_LOCK_SHARED),
+                libsqlite3_sys::SQLITE_OK
+            );
+
+            // A climbs to Reserved, then Pending, uncontested.
+            assert_eq!(
+                raw_lock(&mut conn_a, libsqlite3_sys::SQLITE_LOCK_RESERVED),
+                libsqlite3_sys::SQLITE_OK
+            );
+            assert_eq!(
+                raw_lock(&mut conn_a, libsqlite3_sys::SQLITE_LOCK_PENDING),
+                libsqlite3_sys::SQLITE_OK
+            );
+
+            // A's jump to Exclusive passes through the forced Pending step (already held, so a
+            // no-op), but is refused at the Exclusive step itself because B still holds Shared.
+            assert_eq!(
+                raw_lock(&mut conn_a, libsqlite3_sys::SQLITE_LOCK_EXCLUSIVE),
+                libsqlite3_sys::SQLITE_BUSY
+            );
+
+            // Once B drops its Shared lock, A's retry succeeds.
+            assert_eq!(
+                raw_unlock(&mut conn_b, libsqlite3_sys::SQLITE_LOCK_NONE),
+                libsqlite3_sys::SQLITE_OK
+            );
+            assert_eq!(
+                raw_lock(&mut conn_a, libsqlite3_sys::SQLITE_LOCK_EXCLUSIVE),
+                libsqlite3_sys::SQLITE_OK
+            );
+
+            raw_unlock(&mut conn_a, libsqlite3_sys::SQLITE_LOCK_NONE);
+            raw_close(&mut conn_a);
+            raw_close(&mut conn_b);
+        }
+
+        let _ = fs::remove_file(db_path);
+
+        // Separate path/table: check the Pending-before-Exclusive refusal path itself, where the
+        // forced intermediate Pending acquisition is what fails, and Exclusive is never attempted.
+        let db_path2 = "locktest2.db";
+        let _ = fs::remove_file(db_path2);
+
+        unsafe {
+            let mut conn_a = raw_open(vfs_name, db_path2);
+            let mut conn_b = raw_open(vfs_name, db_path2);
+
+            assert_eq!(
+                raw_lock(&mut conn_a, libsqlite3_sys::SQLITE_LOCK_SHARED),
+                libsqlite3_sys::SQLITE_OK
+            );
+            assert_eq!(
+                raw_lock(&mut conn_a, libsqlite3_sys::SQLITE_LOCK_RESERVED),
+                libsqlite3_sys::SQLITE_OK
+            );
+            assert_eq!(
+                raw_lock(&mut conn_a, libsqlite3_sys::SQLITE_LOCK_PENDING),
+                libsqlite3_sys::SQLITE_OK
+            );
+
+            // B never held so much as a Shared lock; its attempt to jump straight to Exclusive
+            // must fail at the forced Pending step, since A already holds Pending, without ever
+            // reaching the Exclusive acquisition.
+            assert_eq!(
+                raw_lock(&mut conn_b, libsqlite3_sys::SQLITE_LOCK_EXCLUSIVE),
+                libsqlite3_sys::SQLITE_BUSY
+            );
+
+            raw_unlock(&mut conn_a, libsqlite3_sys::SQLITE_LOCK_NONE);
+            raw_close(&mut conn_a);
+            raw_close(&mut conn_b);
+        }
+
+        let _ = fs::remove_file(db_path2);
+    }
+
+    /// How many times the installed `"read"`/`"write"` override has actually been invoked, and
+    /// whether it should intercept the call or decline via [`SQLITE_SYSCALL_PASSTHROUGH`].
+    #[cfg(feature = "syscall")]
+    static FAULT_CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    #[cfg(feature = "syscall")]
+    static FAULT_ACTIVE: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+    #[cfg(feature = "syscall")]
+    unsafe extern "C" fn fault_read(_buf: *mut c_void, _i_amt: c_int, _i_ofst: i64) -> c_int {
+        FAULT_CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        if FAULT_ACTIVE.load(std::sync::atomic::Ordering::SeqCst) {
+            libsqlite3_sys::SQLITE_IOERR_READ
+        } else {
+            crate::SQLITE_SYSCALL_PASSTHROUGH
+        }
+    }
+
+    #[cfg(feature = "syscall")]
+    unsafe extern "C" fn fault_write(_buf: *mut c_void, _i_amt: c_int, _i_ofst: i64) -> c_int {
+        FAULT_CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        if FAULT_ACTIVE.load(std::sync::atomic::Ordering::SeqCst) {
+            libsqlite3_sys::SQLITE_IOERR_WRITE
+        } else {
+            crate::SQLITE_SYSCALL_PASSTHROUGH
+        }
+    }
+
+    #[cfg(feature = "syscall")]
+    #[test]
+    fn test_set_system_call_overrides_read_and_write_then_passthrough_restores_default() {
+        let vfs_name = "dummyvfs_syscall";
+        register(vfs_name, false, DummyVFS).expect("failed to register dummy VFS");
+
+        let db_path = "dummy_syscall.db";
+        let _ = fs::remove_file(db_path);
+
+        unsafe {
+            let mut file = raw_open(vfs_name, db_path);
+
+            // Before any override is installed, xGetSystemCall reports nothing for "read"/"write"
+            // and reads/writes go straight through to the real DatabaseHandle.
+            assert!(raw_get_system_call(vfs_name, "read").is_none());
+            assert_eq!(raw_write(&mut file, b"abcd", 0), libsqlite3_sys::SQLITE_OK);
+
+            let read_override: libsqlite3_sys::sqlite3_syscall_ptr = Some(std::mem::transmute::<
+                unsafe extern "C" fn(*mut c_void, c_int, i64) -> c_int,
+                unsafe extern "C" fn(),
+            >(fault_read));
+            let write_override: libsqlite3_sys::sqlite3_syscall_ptr = Some(std::mem::transmute::<
+                unsafe extern "C" fn(*mut c_void, c_int, i64) -> c_int,
+                unsafe extern "C" fn(),
+            >(fault_write));
+
+            assert_eq!(
+                raw_set_system_call(vfs_name, "read", read_override),
+                libsqlite3_sys::SQLITE_OK
+            );
+            assert_eq!(
+                raw_set_system_call(vfs_name, "write", write_override),
+                libsqlite3_sys::SQLITE_OK
+            );
+            assert!(raw_get_system_call(vfs_name, "read").is_some());
+            assert!(raw_get_system_call(vfs_name, "write").is_some());
+
+            // With the override installed but inactive, it still must be invoked; declining via
+            // SQLITE_SYSCALL_PASSTHROUGH falls through to the real read/write.
+            let calls_before = FAULT_CALLS.load(std::sync::atomic::Ordering::SeqCst);
+            let mut buf = [0u8; 4];
+            assert_eq!(raw_read(&mut file, &mut buf, 0), libsqlite3_sys::SQLITE_OK);
+            assert_eq!(&buf, b"abcd");
+            assert_eq!(raw_write(&mut file, b"efgh", 4), libsqlite3_sys::SQLITE_OK);
+            assert_eq!(
+                FAULT_CALLS.load(std::sync::atomic::Ordering::SeqCst),
+                calls_before + 2,
+                "installed overrides must be invoked even when they decline to intercept"
+            );
+
+            // Activate the override: it now reports an error directly instead of falling through.
+            FAULT_ACTIVE.store(true, std::sync::atomic::Ordering::SeqCst);
+            assert_eq!(raw_read(&mut file, &mut buf, 0), libsqlite3_sys::SQLITE_IOERR_READ);
+            assert_eq!(raw_write(&mut file, b"ijkl", 0), libsqlite3_sys::SQLITE_IOERR_WRITE);
+            FAULT_ACTIVE.store(false, std::sync::atomic::Ordering::SeqCst);
+
+            // Removing the override (xSetSystemCall with a null function pointer) restores the
+            // default DatabaseHandle-backed behavior, and xGetSystemCall reports it as gone.
+            let calls_before_removal = FAULT_CALLS.load(std::sync::atomic::Ordering::SeqCst);
+            assert_eq!(
+                raw_set_system_call(vfs_name, "read", None),
+                libsqlite3_sys::SQLITE_OK
+            );
+            assert_eq!(
+                raw_set_system_call(vfs_name, "write", None),
+                libsqlite3_sys::SQLITE_OK
+            );
+            assert!(raw_get_system_call(vfs_name, "read").is_none());
+            assert!(raw_get_system_call(vfs_name, "write").is_none());
+
+            assert_eq!(raw_read(&mut file, &mut buf, 0), libsqlite3_sys::SQLITE_OK);
+            assert_eq!(&buf, b"abcd");
+            assert_eq!(
+                FAULT_CALLS.load(std::sync::atomic::Ordering::SeqCst),
+                calls_before_removal,
+                "a removed override must not be invoked anymore"
+            );
+
+            raw_close(&mut file);
+        }
+
+        let _ = fs::remove_file(db_path);
+    }
+
+    #[test]
+    fn test_fetch_unfetch_mmap_style_buffer_and_device_info() {
+        let vfs_name = "dummyvfs_fetch";
+        register(vfs_name, false, DummyVFS).expect("failed to register dummy VFS");
+
+        let db_path = "dummy_fetch.db";
+        let _ = fs::remove_file(db_path);
+
+        unsafe {
+            let mut file = raw_open(vfs_name, db_path);
+            assert_eq!(
+                raw_write(&mut file, b"hello world", 0),
+                libsqlite3_sys::SQLITE_OK
+            );
+
+            fn outstanding(file: &mut [u8]) -> usize {
+                unsafe {
+                    crate::file_state::<DummyVFS>(as_sqlite3_file(file))
+                        .unwrap()
+                        .handle
+                        .2
+                        .borrow()
+                        .len()
+                }
+            }
+            assert_eq!(outstanding(&mut file), 0);
+
+            let (rc, ptr) = raw_fetch(&mut file, 0, 5);
+            assert_eq!(rc, libsqlite3_sys::SQLITE_OK);
+            assert!(!ptr.is_null(), "xFetch should hand back a live pointer");
+            assert_eq!(
+                std::slice::from_raw_parts(ptr as *const u8, 5),
+                b"hello"
+            );
+            assert_eq!(outstanding(&mut file), 1, "xFetch must keep the buffer alive until xUnfetch");
+
+            assert_eq!(
+                raw_unfetch(&mut file, 0, ptr),
+                libsqlite3_sys::SQLITE_OK
+            );
+            assert_eq!(outstanding(&mut file), 0, "xUnfetch must release the fetched buffer");
+
+            assert_eq!(
+                raw_device_characteristics(&mut file),
+                libsqlite3_sys::SQLITE_IOCAP_ATOMIC,
+                "xDeviceCharacteristics must surface DatabaseHandle::device_characteristics"
+            );
+            assert_eq!(
+                raw_sector_size(&mut file),
+                4096,
+                "xSectorSize must surface DatabaseHandle::sector_size"
+            );
+
+            raw_close(&mut file);
+        }
+
+        let _ = fs::remove_file(db_path);
+    }
+
+    #[test]
+    fn test_delete_and_access() {
+        let vfs_name = "dummyvfs_delete";
+        register(vfs_name, false, DummyVFS).expect("failed to register dummy VFS");
+
+        let db_path = "dummy_delete.db";
+        let _ = fs::remove_file(db_path);
+
+        unsafe {
+            assert_eq!(
+                raw_access(vfs_name, db_path, libsqlite3_sys::SQLITE_ACCESS_EXISTS),
+                0,
+                "a never-created file must not exist"
+            );
+
+            let mut file = raw_open(vfs_name, db_path);
+            raw_close(&mut file);
+
+            assert_eq!(
+                raw_access(vfs_name, db_path, libsqlite3_sys::SQLITE_ACCESS_EXISTS),
+                1
+            );
+            assert_eq!(
+                raw_access(vfs_name, db_path, libsqlite3_sys::SQLITE_ACCESS_READ),
+                1
+            );
+            assert_eq!(
+                raw_access(vfs_name, db_path, libsqlite3_sys::SQLITE_ACCESS_READWRITE),
+                1
+            );
+
+            assert_eq!(
+                raw_delete(vfs_name, db_path, false),
+                libsqlite3_sys::SQLITE_OK
+            );
+            assert!(
+                fs::metadata(db_path).is_err(),
+                "xDelete must actually remove the file from disk"
+            );
+            assert_eq!(
+                raw_access(vfs_name, db_path, libsqlite3_sys::SQLITE_ACCESS_EXISTS),
+                0
+            );
+
+            // Deleting an already-absent file is reported distinctly, per x_delete<V>.
+            assert_eq!(
+                raw_delete(vfs_name, db_path, false),
+                libsqlite3_sys::SQLITE_IOERR_DELETE_NOENT
+            );
+        }
+
+        let _ = fs::remove_file(db_path);
+    }
 }
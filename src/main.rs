@@ -0,0 +1,16 @@
+use std::sync::OnceLock;
+
+trait Tag { fn name() -> &'static str; }
+struct A; struct B;
+impl Tag for A { fn name() -> &'static str { "A" } }
+impl Tag for B { fn name() -> &'static str { "B" } }
+
+fn get<V: Tag>() -> &'static str {
+    static CACHE: OnceLock<&'static str> = OnceLock::new();
+    CACHE.get_or_init(|| V::name())
+}
+
+fn main() {
+    println!("A -> {}", get::<A>());
+    println!("B -> {}", get::<B>());
+}
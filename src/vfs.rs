@@ -1,6 +1,12 @@
+use std::os::raw::{c_char, c_int};
+use std::time::Duration;
+
+#[cfg(feature = "syscall")]
+use std::ffi::CString;
+
 use crate::*;
 
-pub unsafe extern "C" fn x_open<V: VFS + Sync + Sized>(
+pub unsafe extern "C" fn x_open<V: VFS>(
     arg1: *mut libsqlite3_sys::sqlite3_vfs,
     zName: *const ::std::os::raw::c_char,
     arg2: *mut libsqlite3_sys::sqlite3_file,
@@ -26,8 +32,252 @@ pub unsafe extern "C" fn x_open<V: VFS + Sync + Sized>(
             );
         }
     };
-    // out_file.base.pMethods = &state.io_methods;
-    out_file.ext.write(state.clone());
 
+    let path = match cstr_to_str(zName) {
+        Ok(path) => path,
+        Err(err) => return state.set_last_error(libsqlite3_sys::SQLITE_CANTOPEN, err),
+    };
+
+    let handle = match state.vfs.x_open(path, flags) {
+        Ok(handle) => handle,
+        Err(err) => return state.set_last_error(libsqlite3_sys::SQLITE_CANTOPEN, err),
+    };
+
+    out_file.base.pMethods = state.methods;
+    out_file.ext.write(FileExt {
+        vfs: state.clone(),
+        handle,
+        #[cfg(feature = "wal")]
+        wal_index: None,
+    });
+
+    if !pOutFlags.is_null() {
+        *pOutFlags = flags;
+    }
+
+    libsqlite3_sys::SQLITE_OK
+}
+
+pub unsafe extern "C" fn x_delete<V: VFS>(
+    p_vfs: *mut libsqlite3_sys::sqlite3_vfs,
+    z_path: *const c_char,
+    sync_dir: c_int,
+) -> c_int {
+    let state = match vfs_state::<V>(p_vfs) {
+        Ok(state) => state,
+        Err(_) => return libsqlite3_sys::SQLITE_ERROR,
+    };
+
+    let path = match cstr_to_str(z_path) {
+        Ok(path) => path,
+        Err(err) => return state.set_last_error(libsqlite3_sys::SQLITE_IOERR_DELETE, err),
+    };
+
+    match state.vfs.delete(path, sync_dir != 0) {
+        Ok(()) => libsqlite3_sys::SQLITE_OK,
+        Err(err) if err.kind() == ErrorKind::NotFound => libsqlite3_sys::SQLITE_IOERR_DELETE_NOENT,
+        Err(err) => state.set_last_error(libsqlite3_sys::SQLITE_IOERR_DELETE, err),
+    }
+}
+
+pub unsafe extern "C" fn x_access<V: VFS>(
+    p_vfs: *mut libsqlite3_sys::sqlite3_vfs,
+    z_path: *const c_char,
+    flags: c_int,
+    p_res_out: *mut c_int,
+) -> c_int {
+    let state = match vfs_state::<V>(p_vfs) {
+        Ok(state) => state,
+        Err(_) => return libsqlite3_sys::SQLITE_ERROR,
+    };
+
+    let path = match cstr_to_str(z_path) {
+        Ok(path) => path,
+        Err(err) => return state.set_last_error(libsqlite3_sys::SQLITE_ERROR, err),
+    };
+
+    let access_flags = match flags {
+        libsqlite3_sys::SQLITE_ACCESS_READWRITE => AccessFlags::ReadWrite,
+        libsqlite3_sys::SQLITE_ACCESS_READ => AccessFlags::Read,
+        _ => AccessFlags::Exists,
+    };
+
+    *p_res_out = state.vfs.access(path, access_flags) as c_int;
+    libsqlite3_sys::SQLITE_OK
+}
+
+pub unsafe extern "C" fn x_full_pathname<V: VFS>(
+    p_vfs: *mut libsqlite3_sys::sqlite3_vfs,
+    z_path: *const c_char,
+    n_out: c_int,
+    z_out: *mut c_char,
+) -> c_int {
+    let state = match vfs_state::<V>(p_vfs) {
+        Ok(state) => state,
+        Err(_) => return libsqlite3_sys::SQLITE_ERROR,
+    };
+
+    let path = match cstr_to_str(z_path) {
+        Ok(path) => path,
+        Err(err) => return state.set_last_error(libsqlite3_sys::SQLITE_CANTOPEN, err),
+    };
+
+    if n_out <= 0 {
+        return libsqlite3_sys::SQLITE_CANTOPEN;
+    }
+
+    let out = std::slice::from_raw_parts_mut(z_out as *mut u8, n_out as usize);
+    // Reserve the last byte for the nul terminator before handing the buffer to the VFS impl, so
+    // a `full_pathname` that legitimately fills the whole slice it's given can never collide with
+    // the terminator we write below.
+    let terminator_at = out.len() - 1;
+    let written = state.vfs.full_pathname(path, &mut out[..terminator_at]).len();
+    out[written.min(terminator_at)] = 0;
+
+    libsqlite3_sys::SQLITE_OK
+}
+
+pub unsafe extern "C" fn x_randomness<V: VFS>(
+    p_vfs: *mut libsqlite3_sys::sqlite3_vfs,
+    n_byte: c_int,
+    z_out: *mut c_char,
+) -> c_int {
+    let state = match vfs_state::<V>(p_vfs) {
+        Ok(state) => state,
+        Err(_) => return 0,
+    };
+
+    let buf = std::slice::from_raw_parts_mut(z_out as *mut u8, n_byte.max(0) as usize);
+    state.vfs.randomness(buf);
+    buf.len() as c_int
+}
+
+pub unsafe extern "C" fn x_sleep<V: VFS>(
+    p_vfs: *mut libsqlite3_sys::sqlite3_vfs,
+    microseconds: c_int,
+) -> c_int {
+    let state = match vfs_state::<V>(p_vfs) {
+        Ok(state) => state,
+        Err(_) => return 0,
+    };
+
+    let requested = Duration::from_micros(microseconds.max(0) as u64);
+    let slept = state.vfs.sleep(requested);
+    slept.as_micros() as c_int
+}
+
+pub unsafe extern "C" fn x_current_time<V: VFS>(
+    p_vfs: *mut libsqlite3_sys::sqlite3_vfs,
+    p_time: *mut f64,
+) -> c_int {
+    let state = match vfs_state::<V>(p_vfs) {
+        Ok(state) => state,
+        Err(_) => return libsqlite3_sys::SQLITE_ERROR,
+    };
+
+    *p_time = state.vfs.current_time();
     libsqlite3_sys::SQLITE_OK
 }
+
+pub unsafe extern "C" fn x_get_last_error<V: VFS>(
+    p_vfs: *mut libsqlite3_sys::sqlite3_vfs,
+    n_byte: c_int,
+    z_out: *mut c_char,
+) -> c_int {
+    let state = match vfs_state::<V>(p_vfs) {
+        Ok(state) => state,
+        Err(_) => return libsqlite3_sys::SQLITE_ERROR,
+    };
+
+    let guard = state.last_error.lock().unwrap();
+    let Some((code, err)) = guard.as_ref() else {
+        return libsqlite3_sys::SQLITE_OK;
+    };
+
+    if n_byte > 0 {
+        let out = std::slice::from_raw_parts_mut(z_out as *mut u8, n_byte as usize);
+        let message = err.to_string();
+        let written = message.len().min(out.len() - 1);
+        out[..written].copy_from_slice(&message.as_bytes()[..written]);
+        out[written] = 0;
+    }
+
+    *code
+}
+
+/// Record (or, when `p_new_func` is null, remove) an override for the named system call so test
+/// code can inject faults into SQLite's error-recovery paths. `"read"` and `"write"` overrides are
+/// consulted by `x_read`/`x_write` (see [`IoSyscallOverride`]) before falling through to the real
+/// `DatabaseHandle`; other names are only bookkeeping, returned by `xGetSystemCall`/
+/// `xNextSystemCall` but not otherwise acted on.
+#[cfg(feature = "syscall")]
+pub unsafe extern "C" fn x_set_system_call<V: VFS>(
+    p_vfs: *mut libsqlite3_sys::sqlite3_vfs,
+    z_name: *const c_char,
+    p_new_func: libsqlite3_sys::sqlite3_syscall_ptr,
+) -> c_int {
+    let state = match vfs_state::<V>(p_vfs) {
+        Ok(state) => state,
+        Err(_) => return libsqlite3_sys::SQLITE_ERROR,
+    };
+
+    let name = match cstr_to_str(z_name) {
+        Ok(name) if !name.is_empty() => name,
+        _ => return libsqlite3_sys::SQLITE_NOTFOUND,
+    };
+    let key = CString::new(name).unwrap();
+
+    let mut syscalls = state.syscalls.lock().unwrap();
+    match p_new_func {
+        Some(_) => {
+            syscalls.insert(key, p_new_func);
+        }
+        None => {
+            syscalls.remove(&key);
+        }
+    }
+
+    libsqlite3_sys::SQLITE_OK
+}
+
+#[cfg(feature = "syscall")]
+pub unsafe extern "C" fn x_get_system_call<V: VFS>(
+    p_vfs: *mut libsqlite3_sys::sqlite3_vfs,
+    z_name: *const c_char,
+) -> libsqlite3_sys::sqlite3_syscall_ptr {
+    let state = match vfs_state::<V>(p_vfs) {
+        Ok(state) => state,
+        Err(_) => return None,
+    };
+
+    let name = cstr_to_str(z_name).ok()?;
+    let key = CString::new(name).ok()?;
+    state.syscalls.lock().unwrap().get(&key).copied().flatten()
+}
+
+#[cfg(feature = "syscall")]
+pub unsafe extern "C" fn x_next_system_call<V: VFS>(
+    p_vfs: *mut libsqlite3_sys::sqlite3_vfs,
+    z_name: *const c_char,
+) -> *const c_char {
+    let state = match vfs_state::<V>(p_vfs) {
+        Ok(state) => state,
+        Err(_) => return std::ptr::null(),
+    };
+
+    let syscalls = state.syscalls.lock().unwrap();
+    let mut names: Vec<&CString> = syscalls.keys().collect();
+    names.sort();
+
+    let next = if z_name.is_null() {
+        names.first().copied()
+    } else {
+        cstr_to_str(z_name)
+            .ok()
+            .and_then(|name| CString::new(name).ok())
+            .and_then(|current| names.iter().position(|n| **n == current))
+            .and_then(|pos| names.get(pos + 1).copied())
+    };
+
+    next.map_or(std::ptr::null(), |name| name.as_ptr())
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::pin::Pin;
+
+/// A region of SQLite's WAL shared-memory index, as negotiated through `xShmMap`/`xShmLock`.
+///
+/// Implementors back this with whatever actually needs to be shared between connections: a
+/// memory-mapped file, a segment of real shared memory, or (see [`HeapWalIndex`]) a plain heap
+/// allocation for the common case of every connection living in this process.
+pub trait WalIndex {
+    /// Map the `region`'th page of the WAL index, each `size` bytes, creating it if `create` is
+    /// set and it does not exist yet. The returned pointer stays valid until `unmap`.
+    fn map(&mut self, region: u32, size: usize, create: bool) -> Result<*mut [u8], std::io::Error>;
+
+    /// Acquire or release a byte-range lock over `[offset, offset + n)` of the index. Returns
+    /// `Ok(false)`, rather than an error, when a conflicting lock is already held elsewhere.
+    fn lock(&mut self, offset: u8, n: u8, flags: WalLockFlags) -> Result<bool, std::io::Error>;
+
+    /// A memory fence: writes made before the barrier must be visible to other connections
+    /// before any writes made after it.
+    fn barrier(&mut self);
+
+    /// Release all mapped regions, optionally deleting their backing storage.
+    fn unmap(&mut self, delete: bool) -> Result<(), std::io::Error>;
+}
+
+/// The kind of byte-range lock requested of a [`WalIndex`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalLockFlags {
+    Shared,
+    Exclusive,
+    Unlock,
+}
+
+/// A [`WalIndex`] backed by plain heap allocations.
+///
+/// Each open file gets its own `HeapWalIndex`, so the index is not shared between connections,
+/// not even between connections in this process, and locking is a no-op. This is enough for WAL
+/// mode with one connection per database file.
+#[derive(Default)]
+pub struct HeapWalIndex {
+    regions: HashMap<u32, Pin<Box<[u8]>>>,
+}
+
+impl WalIndex for HeapWalIndex {
+    fn map(&mut self, region: u32, size: usize, create: bool) -> Result<*mut [u8], std::io::Error> {
+        let region = match self.regions.entry(region) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                if !create {
+                    return Err(std::io::Error::new(
+                        ErrorKind::NotFound,
+                        "wal index region not mapped",
+                    ));
+                }
+                entry.insert(Pin::new(vec![0u8; size].into_boxed_slice()))
+            }
+        };
+
+        Ok(Pin::get_mut(region.as_mut()) as *mut [u8])
+    }
+
+    fn lock(&mut self, _offset: u8, _n: u8, _flags: WalLockFlags) -> Result<bool, std::io::Error> {
+        // Regions are private to one open file, so there is nothing to contend over.
+        Ok(true)
+    }
+
+    fn barrier(&mut self) {
+        std::sync::atomic::fence(std::sync::atomic::Ordering::SeqCst);
+    }
+
+    fn unmap(&mut self, _delete: bool) -> Result<(), std::io::Error> {
+        self.regions.clear();
+        Ok(())
+    }
+}